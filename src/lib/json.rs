@@ -0,0 +1,114 @@
+use super::Builder;
+
+/// An in-memory JSON value.
+///
+/// Values serialize straight into a [`Builder`] via [`Json::serialize`], so
+/// responses are assembled without first rendering the whole document into a
+/// `String`; numbers and `\u00XX` escapes are still formatted via `format!`.
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Write this value into `builder` as RFC8259 JSON text.
+    pub fn serialize(&self, builder: &mut Builder) {
+        match self {
+            Json::Null => builder.append("null"),
+            Json::Bool(true) => builder.append("true"),
+            Json::Bool(false) => builder.append("false"),
+            Json::Number(n) => serialize_number(*n, builder),
+            Json::String(s) => serialize_string(s, builder),
+            Json::Array(items) => {
+                builder.append("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        builder.append(",");
+                    }
+                    item.serialize(builder);
+                }
+                builder.append("]");
+            }
+            Json::Object(entries) => {
+                builder.append("{");
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        builder.append(",");
+                    }
+                    serialize_string(key, builder);
+                    builder.append(":");
+                    value.serialize(builder);
+                }
+                builder.append("}");
+            }
+        }
+    }
+}
+
+/// Numbers lose their fractional part when they are integral so `1.0`
+/// serializes as `1`; non-finite values are not representable and become
+/// `null`, matching the usual JSON convention.
+fn serialize_number(n: f64, builder: &mut Builder) {
+    if !n.is_finite() {
+        builder.append("null");
+    } else if n.fract() == 0.0 && n.abs() < 1e15 {
+        builder.append(format!("{}", n as i64));
+    } else {
+        builder.append(format!("{n}"));
+    }
+}
+
+/// Write `value` as a quoted, escaped JSON string.
+fn serialize_string(value: &str, builder: &mut Builder) {
+    builder.append("\"");
+    for c in value.chars() {
+        match c {
+            '"' => builder.append("\\\""),
+            '\\' => builder.append("\\\\"),
+            '\n' => builder.append("\\n"),
+            '\r' => builder.append("\\r"),
+            '\t' => builder.append("\\t"),
+            '\u{08}' => builder.append("\\b"),
+            '\u{0C}' => builder.append("\\f"),
+            c if (c as u32) < 0x20 => builder.append(format!("\\u{:04x}", c as u32)),
+            c => builder.append(c),
+        }
+    }
+    builder.append("\"");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_string(value: &Json) -> String {
+        let mut builder = Builder::default();
+        value.serialize(&mut builder);
+        builder.string().unwrap()
+    }
+
+    #[test]
+    fn escapes_control_and_reserved_characters() {
+        let value = Json::String("a\"b\\c\nd\re\tf\u{08}g\u{0C}h\u{01}i".to_string());
+        assert_eq!(to_string(&value), "\"a\\\"b\\\\c\\nd\\re\\tf\\bg\\fh\\u0001i\"");
+    }
+
+    #[test]
+    fn serializes_object_and_array() {
+        let value = Json::Object(vec![
+            ("n".to_string(), Json::Number(1.0)),
+            ("a".to_string(), Json::Array(vec![Json::Bool(true), Json::Null])),
+        ]);
+        assert_eq!(to_string(&value), "{\"n\":1,\"a\":[true,null]}");
+    }
+
+    #[test]
+    fn non_finite_numbers_serialize_as_null() {
+        assert_eq!(to_string(&Json::Number(f64::NAN)), "null");
+        assert_eq!(to_string(&Json::Number(f64::INFINITY)), "null");
+    }
+}
@@ -0,0 +1,12 @@
+pub mod json;
+pub mod plugin;
+pub mod profiler;
+pub mod stringbuilder;
+pub mod router;
+pub mod websocket;
+
+pub use stringbuilder::Builder;
+pub use json::Json;
+pub use router::{RequestHandler, Response, Router};
+pub use plugin::{PluginRegistry, HANDLER_API_VERSION};
+pub use websocket::Message;
@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::sync::Arc;
+
+use libloading::{Library, Symbol};
+
+use super::router::{RequestHandler, Response, Router};
+
+/// ABI version exported handlers must agree on. Bump this whenever the
+/// `run_handler` signature or buffer ownership contract changes.
+pub const HANDLER_API_VERSION: i32 = 1;
+
+type ApiVersionFn = unsafe extern "C" fn() -> c_int;
+type RunHandlerFn = unsafe extern "C" fn(
+    *const u8,
+    u64,
+    *const u8,
+    u64,
+    *const u8,
+    u64,
+    *mut *mut u8,
+    *mut u64,
+) -> c_int;
+type FreeResponseFn = unsafe extern "C" fn(*mut u8, u64);
+
+/// A handler backed by a symbol resolved from a dynamic library.
+///
+/// The [`Library`] is kept behind an [`Arc`] so it outlives every request that
+/// dispatches into it — unloading while a worker is mid-call would be undefined
+/// behavior.
+pub struct Plugin {
+    _library: Arc<Library>,
+    run_handler: RawSymbol<RunHandlerFn>,
+    free_response: RawSymbol<FreeResponseFn>,
+}
+
+/// A symbol detached from its [`Library`] borrow; kept alive by the `Arc` the
+/// owning [`Plugin`] holds alongside it.
+struct RawSymbol<T>(T);
+
+// Safe to share because the backing `Library` is pinned for the process
+// lifetime and the exported functions are reentrant by contract.
+unsafe impl<T> Send for RawSymbol<T> {}
+unsafe impl<T> Sync for RawSymbol<T> {}
+
+impl RequestHandler for Plugin {
+    fn handle(&self, method: &str, path: &str, _headers: &[&str], body: &[u8]) -> Response {
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: u64 = 0;
+
+        let status = unsafe {
+            (self.run_handler.0)(
+                method.as_ptr(),
+                method.len() as u64,
+                path.as_ptr(),
+                path.len() as u64,
+                body.as_ptr(),
+                body.len() as u64,
+                &mut out_ptr,
+                &mut out_len,
+            )
+        };
+
+        if status < 0 || out_ptr.is_null() {
+            return Response::new(500);
+        }
+
+        let mut response = Response::new(status as u16);
+        unsafe {
+            let bytes = std::slice::from_raw_parts(out_ptr, out_len as usize);
+            response.write(bytes);
+            (self.free_response.0)(out_ptr, out_len);
+        }
+        response
+    }
+}
+
+/// Owns the loaded libraries and mounts their handlers onto a [`Router`].
+#[derive(Default)]
+pub struct PluginRegistry {
+    libraries: Vec<Arc<Library>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> PluginRegistry {
+        PluginRegistry::default()
+    }
+
+    /// Load `path`, verify its ABI version and mount `run_handler` under
+    /// `(method, mount)` on `router`.
+    pub fn load<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        method: &str,
+        mount: &str,
+        router: &mut Router,
+    ) -> Result<(), Box<dyn Error>> {
+        let library = unsafe { Library::new(path.as_ref())? };
+
+        unsafe {
+            let version: Symbol<ApiVersionFn> = library.get(b"handler_api_version")?;
+            let reported = version();
+            if reported != HANDLER_API_VERSION {
+                return Err(format!(
+                    "handler ABI mismatch: library reports {reported}, expected {HANDLER_API_VERSION}"
+                )
+                .into());
+            }
+
+            let run_handler: Symbol<RunHandlerFn> = library.get(b"run_handler")?;
+            let free_response: Symbol<FreeResponseFn> = library.get(b"free_response")?;
+
+            let run_handler = RawSymbol(*run_handler.into_raw());
+            let free_response = RawSymbol(*free_response.into_raw());
+
+            let library = Arc::new(library);
+            self.libraries.push(Arc::clone(&library));
+
+            router.route(
+                method,
+                mount,
+                Plugin {
+                    _library: library,
+                    run_handler,
+                    free_response,
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
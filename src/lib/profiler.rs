@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use super::Builder;
+
+/// How often the interval timer fires `SIGPROF`, in microseconds (~100 Hz).
+const SAMPLE_INTERVAL_US: i64 = 10_000;
+
+/// A captured stack aggregated by identical frame lists.
+type StackCounts = HashMap<Vec<String>, u64>;
+
+/// Gates whether the signal handler records samples. Sampling is a
+/// process-wide, one-at-a-time operation driven by [`sample`].
+static SAMPLING: AtomicBool = AtomicBool::new(false);
+
+/// Accumulates `stack -> count` while sampling is active.
+static STACKS: Mutex<Option<StackCounts>> = Mutex::new(None);
+
+/// Sample the calling process for `seconds`, returning the aggregated
+/// `stack -> count` map. Only one sampling window runs at a time; concurrent
+/// calls serialize on the profiler state.
+pub fn sample(seconds: u64) -> StackCounts {
+    static WINDOW: Mutex<()> = Mutex::new(());
+    let _window = WINDOW.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    install_handler();
+    *STACKS.lock().unwrap() = Some(HashMap::new());
+
+    set_timer(SAMPLE_INTERVAL_US);
+    SAMPLING.store(true, Ordering::SeqCst);
+
+    thread::sleep(Duration::from_secs(seconds));
+
+    SAMPLING.store(false, Ordering::SeqCst);
+    set_timer(0);
+
+    STACKS.lock().unwrap().take().unwrap_or_default()
+}
+
+/// `SIGPROF` handler: walk the current stack and bump its count. The work done
+/// here (allocation, locking) is not strictly async-signal-safe, but matches
+/// the lightweight-profiler tradeoff the subsystem targets.
+extern "C" fn on_sigprof(_sig: i32) {
+    if !SAMPLING.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let mut frames = Vec::new();
+    backtrace::trace(|frame| {
+        backtrace::resolve_frame(frame, |symbol| {
+            if let Some(name) = symbol.name() {
+                frames.push(name.to_string());
+            }
+        });
+        true
+    });
+    // Root-to-leaf so folded output reads top-down.
+    frames.reverse();
+
+    if let Ok(mut guard) = STACKS.try_lock() {
+        if let Some(map) = guard.as_mut() {
+            *map.entry(frames).or_insert(0) += 1;
+        }
+    }
+}
+
+fn install_handler() {
+    unsafe {
+        libc::signal(libc::SIGPROF, on_sigprof as *const () as libc::sighandler_t);
+    }
+}
+
+/// Arm (non-zero) or disarm (zero) the `ITIMER_PROF` interval timer.
+fn set_timer(interval_us: i64) {
+    let timer = libc::itimerval {
+        it_interval: libc::timeval { tv_sec: 0, tv_usec: interval_us },
+        it_value: libc::timeval { tv_sec: 0, tv_usec: interval_us },
+    };
+    unsafe {
+        libc::setitimer(libc::ITIMER_PROF, &timer, std::ptr::null_mut());
+    }
+}
+
+/// Render folded stacks: one `frame;frame;frame count` line per unique stack.
+pub fn folded(stacks: &StackCounts) -> String {
+    let mut builder = Builder::default();
+    for (stack, count) in stacks {
+        builder.append(stack.join(";"));
+        builder.append(format!(" {count}\n"));
+    }
+    builder.string().unwrap()
+}
+
+/// Encode the aggregated stacks as a `perftools.profiles.Profile` protobuf
+/// (the format pprof and speedscope consume).
+pub fn pprof(stacks: &StackCounts) -> Vec<u8> {
+    let mut pb = Protobuf::default();
+
+    // The string table must start with the empty string.
+    let mut strings = StringTable::default();
+    strings.intern("");
+    let samples_idx = strings.intern("samples");
+    let count_idx = strings.intern("count");
+
+    // sample_type { type, unit }
+    let mut value_type = Protobuf::default();
+    value_type.int64(1, samples_idx as i64);
+    value_type.int64(2, count_idx as i64);
+    pb.message(1, &value_type);
+
+    let mut functions = Protobuf::default();
+    let mut locations = Protobuf::default();
+    let mut function_ids: HashMap<String, u64> = HashMap::new();
+
+    for (stack, count) in stacks {
+        let mut sample = Protobuf::default();
+        let mut location_ids = Protobuf::default();
+
+        for frame in stack {
+            let next_id = function_ids.len() as u64 + 1;
+            let id = *function_ids.entry(frame.clone()).or_insert_with(|| {
+                let id = next_id;
+                let name_idx = strings.intern(frame);
+
+                let mut function = Protobuf::default();
+                function.uint64(1, id);
+                function.int64(2, name_idx as i64);
+                functions.message(5, &function);
+
+                let mut line = Protobuf::default();
+                line.uint64(1, id);
+                let mut location = Protobuf::default();
+                location.uint64(1, id);
+                location.message(4, &line);
+                locations.message(4, &location);
+
+                id
+            });
+            location_ids.uint64_packed(id);
+        }
+
+        sample.packed(1, &location_ids);
+        sample.int64(2, *count as i64);
+        pb.message(2, &sample);
+    }
+
+    pb.raw(locations.take());
+    pb.raw(functions.take());
+    for entry in strings.entries() {
+        pb.string(6, entry);
+    }
+
+    pb.take()
+}
+
+/// Interns strings into a pprof string table, preserving insertion order.
+#[derive(Default)]
+struct StringTable {
+    index: HashMap<String, u64>,
+    order: Vec<String>,
+}
+
+impl StringTable {
+    fn intern(&mut self, value: &str) -> u64 {
+        if let Some(&idx) = self.index.get(value) {
+            return idx;
+        }
+        let idx = self.order.len() as u64;
+        self.index.insert(value.to_string(), idx);
+        self.order.push(value.to_string());
+        idx
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &str> {
+        self.order.iter().map(String::as_str)
+    }
+}
+
+/// Minimal protobuf writer covering the wire types the profile needs.
+#[derive(Default)]
+struct Protobuf(Vec<u8>);
+
+impl Protobuf {
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let mut byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value != 0 {
+                byte |= 0x80;
+            }
+            self.0.push(byte);
+            if value == 0 {
+                break;
+            }
+        }
+    }
+
+    fn tag(&mut self, field: u32, wire: u32) {
+        self.varint(((field << 3) | wire) as u64);
+    }
+
+    fn uint64(&mut self, field: u32, value: u64) {
+        self.tag(field, 0);
+        self.varint(value);
+    }
+
+    fn int64(&mut self, field: u32, value: i64) {
+        self.tag(field, 0);
+        self.varint(value as u64);
+    }
+
+    /// Append a bare varint to a packed field's payload buffer.
+    fn uint64_packed(&mut self, value: u64) {
+        self.varint(value);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.tag(field, 2);
+        self.varint(value.len() as u64);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn message(&mut self, field: u32, message: &Protobuf) {
+        self.tag(field, 2);
+        self.varint(message.0.len() as u64);
+        self.0.extend_from_slice(&message.0);
+    }
+
+    fn packed(&mut self, field: u32, payload: &Protobuf) {
+        self.tag(field, 2);
+        self.varint(payload.0.len() as u64);
+        self.0.extend_from_slice(&payload.0);
+    }
+
+    /// Splice pre-encoded field bytes (already tagged) into this message.
+    fn raw(&mut self, bytes: Vec<u8>) {
+        self.0.extend_from_slice(&bytes);
+    }
+
+    fn take(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode the top-level fields of a protobuf message as (field, value)
+    /// pairs, where value is the varint for wire type 0 and the payload length
+    /// for wire type 2, enough to assert on the shape `pprof` produces without
+    /// re-implementing a full protobuf reader.
+    fn decode_tags(mut bytes: &[u8]) -> Vec<(u32, u64)> {
+        let mut tags = Vec::new();
+        while !bytes.is_empty() {
+            let (key, rest) = read_varint(bytes);
+            bytes = rest;
+            let field = (key >> 3) as u32;
+            let wire = key & 0x7;
+            match wire {
+                0 => {
+                    let (value, rest) = read_varint(bytes);
+                    bytes = rest;
+                    tags.push((field, value));
+                }
+                2 => {
+                    let (len, rest) = read_varint(bytes);
+                    bytes = rest;
+                    tags.push((field, len));
+                    bytes = &bytes[len as usize..];
+                }
+                _ => panic!("unexpected wire type {wire}"),
+            }
+        }
+        tags
+    }
+
+    fn read_varint(bytes: &[u8]) -> (u64, &[u8]) {
+        let mut value = 0u64;
+        let mut shift = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return (value, &bytes[i + 1..]);
+            }
+            shift += 7;
+        }
+        panic!("truncated varint");
+    }
+
+    #[test]
+    fn pprof_encodes_one_sample_per_stack_with_a_leading_empty_string() {
+        let mut stacks = StackCounts::default();
+        stacks.insert(vec!["main".to_string(), "work".to_string()], 3);
+
+        let bytes = pprof(&stacks);
+        let tags = decode_tags(&bytes);
+
+        // sample_type (1), sample (2), location (4, once per frame), function
+        // (5, once per frame) and string_table (6, starting with "").
+        assert_eq!(tags.iter().filter(|(field, _)| *field == 1).count(), 1);
+        assert_eq!(tags.iter().filter(|(field, _)| *field == 2).count(), 1);
+        assert_eq!(tags.iter().filter(|(field, _)| *field == 4).count(), 2);
+        assert_eq!(tags.iter().filter(|(field, _)| *field == 5).count(), 2);
+
+        let string_table_lens: Vec<u64> = tags
+            .iter()
+            .filter(|(field, _)| *field == 6)
+            .map(|(_, len)| *len)
+            .collect();
+        assert_eq!(string_table_lens[0], 0, "string table must start with the empty string");
+    }
+
+    #[test]
+    fn folded_formats_stack_count_lines() {
+        let mut stacks = StackCounts::default();
+        stacks.insert(vec!["main".to_string(), "work".to_string()], 2);
+        assert_eq!(folded(&stacks), "main;work 2\n");
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use super::Builder;
+
+/// A response produced by a [`RequestHandler`].
+///
+/// The body is accumulated through the crate's [`Builder`]; [`Response::into_bytes`]
+/// copies it after the status line and headers into the buffer written to the socket.
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Builder,
+}
+
+impl Default for Response {
+    fn default() -> Response {
+        Response {
+            status: 200,
+            headers: Vec::new(),
+            body: Builder::default(),
+        }
+    }
+}
+
+impl Response {
+    pub fn new(status: u16) -> Response {
+        Response {
+            status,
+            ..Response::default()
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Append bytes to the response body via the underlying [`Builder`].
+    pub fn write<T: super::stringbuilder::ToBytes>(&mut self, buf: T) {
+        self.body.append(buf);
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Serialize the status line, headers and body into a single buffer.
+    ///
+    /// The body is carried through as raw bytes rather than `String` so
+    /// binary responses (e.g. the pprof profile format) round-trip intact.
+    pub fn into_bytes(self, reason: &str) -> Vec<u8> {
+        let body = self.body.into_bytes();
+        let mut out = Builder::default();
+        out.append(format!("HTTP/1.1 {} {}\r\n", self.status, reason));
+        out.append(format!("Content-Length: {}\r\n", body.len()));
+        for (name, value) in &self.headers {
+            out.append(format!("{name}: {value}\r\n"));
+        }
+        out.append("\r\n");
+        out.append(body.as_slice());
+        out.into_bytes()
+    }
+}
+
+/// Something that can turn a parsed request into a [`Response`].
+pub trait RequestHandler: Send + Sync {
+    fn handle(&self, method: &str, path: &str, headers: &[&str], body: &[u8]) -> Response;
+}
+
+impl<F> RequestHandler for F
+where
+    F: Fn(&str, &str, &[&str], &[u8]) -> Response + Send + Sync,
+{
+    fn handle(&self, method: &str, path: &str, headers: &[&str], body: &[u8]) -> Response {
+        self(method, path, headers, body)
+    }
+}
+
+/// Maps `(method, path)` pairs to the handler that serves them.
+#[derive(Default)]
+pub struct Router {
+    routes: HashMap<(String, String), Box<dyn RequestHandler>>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Register `handler` for an exact `(method, path)` pair.
+    pub fn route<H: RequestHandler + 'static>(&mut self, method: &str, path: &str, handler: H) {
+        self.routes
+            .insert((method.to_string(), path.to_string()), Box::new(handler));
+    }
+
+    /// Dispatch a parsed request: `404` when `path` matches no route at all,
+    /// `405` when `path` is known but not for `method`.
+    ///
+    /// Routes are matched on the path component only — a query string (the
+    /// part from `?` onward) does not affect matching, so a route registered
+    /// for `/` also serves `GET /?x=1`. `path` is still passed to the handler
+    /// unmodified so it can inspect the query string itself.
+    pub fn dispatch(&self, method: &str, path: &str, headers: &[&str], body: &[u8]) -> Response {
+        let route = path.split_once('?').map_or(path, |(route, _)| route);
+        match self.routes.get(&(method.to_string(), route.to_string())) {
+            Some(handler) => handler.handle(method, path, headers, body),
+            None if self.routes.keys().any(|(_, p)| p == route) => Response::new(405),
+            None => Response::new(404),
+        }
+    }
+}
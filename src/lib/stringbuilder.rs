@@ -30,9 +30,17 @@ impl Builder {
         self.0.len()
     }
 
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
     pub fn string(self) -> Result<String, FromUtf8Error> {
         String::from_utf8(self.0)
     }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
 }
 
 pub trait ToBytes {
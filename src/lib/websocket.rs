@@ -0,0 +1,284 @@
+use std::io::{self, Read, Write};
+
+use super::Builder;
+
+/// The magic GUID RFC6455 appends to the client key before hashing.
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A decoded WebSocket message handed to the user callback.
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// Compute the `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(client_key: &str) -> String {
+    let mut input = Builder::default();
+    input.append(client_key);
+    input.append(WS_GUID);
+    base64_encode(&sha1(&input.into_bytes()))
+}
+
+/// Write the `101 Switching Protocols` handshake response to `stream`.
+pub fn handshake<S: Write>(stream: &mut S, client_key: &str) -> io::Result<()> {
+    let mut response = Builder::default();
+    response.append("HTTP/1.1 101 Switching Protocols\r\n");
+    response.append("Upgrade: websocket\r\n");
+    response.append("Connection: Upgrade\r\n");
+    response.append(format!("Sec-WebSocket-Accept: {}\r\n", accept_key(client_key)));
+    response.append("\r\n");
+    stream.write_all(&response.into_bytes())
+}
+
+/// Drive the frame loop, invoking `on_message` for every decoded frame,
+/// control frames (ping, pong, close) included.
+///
+/// Ping frames are answered with a pong automatically and the loop terminates
+/// when a close frame, EOF or an unsupported opcode arrives. This keeps the socket open for
+/// push-style traffic instead of the single read-then-write flow.
+pub fn serve<S, F>(stream: &mut S, mut on_message: F) -> io::Result<()>
+where
+    S: Read + Write,
+    F: FnMut(Message),
+{
+    loop {
+        match read_frame(stream)? {
+            Some(Message::Close) => {
+                write_frame(stream, 0x8, &[])?;
+                on_message(Message::Close);
+                break;
+            }
+            Some(Message::Ping(payload)) => {
+                write_frame(stream, 0xA, &payload)?;
+                on_message(Message::Ping(payload));
+            }
+            Some(message) => on_message(message),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// Upper bound on a single frame's declared payload length. The length field
+/// is attacker-controlled, so without a cap a single header can demand an
+/// arbitrarily large `vec![0u8; len]` allocation before any payload arrives.
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Read a single client frame, returning `None` on a clean EOF.
+///
+/// Fragmented messages are not reassembled: the FIN bit is ignored, so a
+/// first fragment is returned as if it were a whole message, and continuation
+/// frames (opcode `0x0`) fall into the catch-all arm below and end the
+/// connection, same as any other opcode we don't recognize.
+fn read_frame<S: Read>(stream: &mut S) -> io::Result<Option<Message>> {
+    let mut header = [0u8; 2];
+    if !read_exact_or_eof(stream, &mut header)? {
+        return Ok(None);
+    }
+
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let mut len = (header[1] & 0x7F) as usize;
+
+    if len == 126 {
+        let mut ext = [0u8; 2];
+        stream.read_exact(&mut ext)?;
+        len = u16::from_be_bytes(ext) as usize;
+    } else if len == 127 {
+        let mut ext = [0u8; 8];
+        stream.read_exact(&mut ext)?;
+        len = u64::from_be_bytes(ext) as usize;
+    }
+
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame length {len} exceeds maximum of {MAX_FRAME_LEN}"),
+        ));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    let message = match opcode {
+        0x1 => Message::Text(String::from_utf8_lossy(&payload).into_owned()),
+        0x2 => Message::Binary(payload),
+        0x8 => Message::Close,
+        0x9 => Message::Ping(payload),
+        0xA => Message::Pong(payload),
+        _ => return Ok(None),
+    };
+    Ok(Some(message))
+}
+
+/// Write an unmasked server frame with the given opcode and payload.
+fn write_frame<S: Write>(stream: &mut S, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Builder::default();
+    frame.append(0x80 | opcode);
+
+    let len = payload.len();
+    if len < 126 {
+        frame.append(len as u8);
+    } else if len <= u16::MAX as usize {
+        frame.append(126u8);
+        for byte in (len as u16).to_be_bytes() {
+            frame.append(byte);
+        }
+    } else {
+        frame.append(127u8);
+        for byte in (len as u64).to_be_bytes() {
+            frame.append(byte);
+        }
+    }
+
+    frame.append(payload);
+    stream.write_all(&frame.into_bytes())
+}
+
+/// Read exactly `buf.len()` bytes, returning `false` if the peer closed before
+/// any byte was read.
+fn read_exact_or_eof<S: Read>(stream: &mut S, buf: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < buf.len() {
+        match stream.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Minimal SHA-1 over `data`, returning the 20-byte digest.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&bit_len.to_be_bytes());
+
+    for chunk in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in w.iter_mut().take(16).enumerate() {
+            let j = i * 4;
+            *word = u32::from_be_bytes([chunk[j], chunk[j + 1], chunk[j + 2], chunk[j + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | ((!b) & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let tmp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = tmp;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard (RFC4648) base64 encoding with padding.
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+
+        out.push(ALPHABET[(triple >> 18 & 0x3F) as usize] as char);
+        out.push(ALPHABET[(triple >> 12 & 0x3F) as usize] as char);
+        out.push(if chunk.len() > 1 {
+            ALPHABET[(triple >> 6 & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if chunk.len() > 2 {
+            ALPHABET[(triple & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_matches_known_digest() {
+        // https://www.rfc-editor.org/rfc/rfc3174 test vector for "abc".
+        let digest = sha1(b"abc");
+        assert_eq!(
+            digest,
+            [
+                0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50,
+                0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d,
+            ]
+        );
+    }
+
+    #[test]
+    fn base64_encode_matches_known_vectors() {
+        assert_eq!(base64_encode(b"Man"), "TWFu");
+        assert_eq!(base64_encode(b"Ma"), "TWE=");
+        assert_eq!(base64_encode(b"M"), "TQ==");
+        assert_eq!(base64_encode(b""), "");
+    }
+
+    #[test]
+    fn accept_key_matches_rfc6455_example() {
+        // https://www.rfc-editor.org/rfc/rfc6455#section-1.3
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+}
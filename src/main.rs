@@ -1,11 +1,15 @@
 pub mod lib;
 
 use std::{
-    io::prelude::*,
-    net::{TcpListener, TcpStream}, thread, sync::{mpsc, Arc, Mutex}, collections::HashMap, string::FromUtf8Error
+    io::{self, prelude::*, ErrorKind},
+    net::{TcpListener, TcpStream}, thread, time::Duration,
+    sync::{mpsc, atomic::{AtomicBool, Ordering}, Arc, Mutex},
+    collections::HashMap, string::FromUtf8Error
 };
 
 use lib::Builder;
+use lib::{Json, Message, PluginRegistry, Response, Router};
+use lib::{profiler, websocket};
 
 
 pub struct ThreadPool {
@@ -41,11 +45,11 @@ impl ThreadPool {
 
             self.sender.as_ref().unwrap().send(job).unwrap();
         }
-    
-}
 
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
+    /// Stop accepting new jobs, let in-flight jobs drain, and join every
+    /// worker. Idempotent — repeated calls (including the one from `Drop`)
+    /// are no-ops once the workers have been joined.
+    pub fn shutdown(&mut self) {
         drop(self.sender.take());
 
         for worker in &mut self.workers {
@@ -56,6 +60,12 @@ impl Drop for ThreadPool {
     }
 }
 
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
 struct Worker {
     _id: usize,
     thread: Option<thread::JoinHandle<()>>,
@@ -115,81 +125,418 @@ trait ToJson {
 
 impl ToJson for HashMap<&str, &str> {
     fn to_json(&self) -> Result<String, FromUtf8Error> {
+        let entries = self
+            .iter()
+            .map(|(key, value)| (key.to_string(), Json::String(value.to_string())))
+            .collect();
+
         let mut json_builder = Builder::default();
-        let mut i = 0;
-        json_builder.append("{");
-        for (key, value) in self {
-            json_builder.append(format!(
-                "{:?}:{:?}{}",
-                key,
-                value,
-                if i == self.len() - 1 { "" } else { "," }));
-            i += 1;
-        }
-        json_builder.append("}");
+        Json::Object(entries).serialize(&mut json_builder);
         json_builder.string()
     }
 }
 
-fn main() {
-    let listener = TcpListener::bind("127.0.0.1:3000").unwrap();
-    let pool = ThreadPool::new(4);
+fn header_value<'a>(headers: &[&'a str], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find_map(|line| line.split_once(':').filter(|(k, _)| k.trim().eq_ignore_ascii_case(name)))
+        .map(|(_, v)| v.trim())
+}
 
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
+fn wants_websocket(headers: &[&str]) -> bool {
+    header_value(headers, "Upgrade")
+        .map(|v| v.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false)
+}
 
-        pool.execute(|| {
-            handle_connection(stream);
-        });
+fn websocket_key<'a>(headers: &[&'a str]) -> Option<&'a str> {
+    header_value(headers, "Sec-WebSocket-Key")
+}
+
+/// Perform the handshake and keep the socket open, printing data frames to stdout.
+fn handle_websocket(stream: &mut TcpStream, key: &str) {
+    if websocket::handshake(stream, key).is_err() {
+        return;
+    }
+
+    let _ = websocket::serve(stream, |message| match message {
+        Message::Text(text) => println!("ws text: {text}"),
+        Message::Binary(bytes) => println!("ws binary: {} bytes", bytes.len()),
+        _ => {}
+    });
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .find_map(|pair| pair.split_once('=').filter(|(key, _)| *key == name))
+        .map(|(_, value)| value)
+}
+
+/// Upper bound on the sampling window a caller can request, so a single
+/// request cannot pin a worker thread for an unbounded amount of time.
+const MAX_PROFILE_SECONDS: u64 = 30;
+
+/// `SIGPROF` sampling allocates and locks a mutex inside the signal handler,
+/// which is not async-signal-safe. The endpoint is therefore off by default;
+/// set `MITOSIS_ENABLE_PROFILING=1` to opt in.
+fn profiling_enabled() -> bool {
+    std::env::var("MITOSIS_ENABLE_PROFILING").as_deref() == Ok("1")
+}
+
+/// Serve `GET /debug/profile?seconds=N`, sampling the worker threads for the
+/// requested window and returning folded stacks (default) or a pprof profile
+/// (`&format=pprof`). Returns `None` when the request is not `GET` on the
+/// profile endpoint, and a `404` when profiling has not been enabled via
+/// [`profiling_enabled`].
+fn maybe_profile(method: &str, path: &str) -> Option<Response> {
+    let (route, query) = path.split_once('?').unwrap_or((path, ""));
+    if method != "GET" || route != "/debug/profile" {
+        return None;
+    }
+    if !profiling_enabled() {
+        return Some(Response::new(404));
     }
 
-    println!("Shutting down.")
+    let seconds = query_param(query, "seconds")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(5)
+        .min(MAX_PROFILE_SECONDS);
+    let stacks = profiler::sample(seconds);
+
+    let response = if query_param(query, "format") == Some("pprof") {
+        let mut response = Response::new(200).header("Content-Type", "application/octet-stream");
+        let profile = profiler::pprof(&stacks);
+        response.write(profile.as_slice());
+        response
+    } else {
+        let mut response = Response::new(200).header("Content-Type", "text/plain");
+        response.write(profiler::folded(&stacks));
+        response
+    };
+    Some(response)
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let mut buffer = [0; 1024];
+fn reason_phrase(code: u16) -> &'static str {
+    match code {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "",
+    }
+}
 
-    stream.read(&mut buffer).unwrap();
+fn router() -> Router {
+    let mut router = Router::new();
 
-    let request = String::from_utf8_lossy(&buffer).into_owned();
+    router.route("HEAD", "/", |_method: &str, _path: &str, _headers: &[&str], _body: &[u8]| {
+        Response::new(200)
+    });
 
-    let request: Vec<_> = request
-        .trim_matches(char::from(0))
-        .split("\r\n")
-        .collect();
+    router.route("GET", "/", |_method: &str, _path: &str, _headers: &[&str], _body: &[u8]| {
+        let mut json_res: HashMap<&str, &str> = HashMap::new();
+        json_res.insert("hello", "world");
+        json_res.insert("test", "ing");
 
-    let request_line: Vec<_> = request[0].split(" ").collect();
+        let mut response = Response::new(200);
+        response.write(json_res.to_json().unwrap());
+        response
+    });
 
-    let mut headers = Vec::new();
+    router.route("POST", "/", |method: &str, _path: &str, _headers: &[&str], _body: &[u8]| {
+        let mut response = Response::new(200);
+        response.write(format!("{{\"request_method\": \"{method}\"}}"));
+        response
+    });
 
-    for i in 1..request.len() {
-        if request[i].is_empty() { break; }
-        headers.push(request[i]);
+    router
+}
+
+/// Mount any handler libraries listed in `MITOSIS_PLUGINS` (a
+/// `:`-separated list of `path,METHOD,/mount` entries).
+fn load_plugins(registry: &mut PluginRegistry, router: &mut Router) {
+    let Ok(spec) = std::env::var("MITOSIS_PLUGINS") else { return };
+
+    for entry in spec.split(':').filter(|e| !e.is_empty()) {
+        let parts: Vec<_> = entry.split(',').collect();
+        if let [path, method, mount] = parts[..] {
+            if let Err(err) = registry.load(path, method, mount, router) {
+                eprintln!("failed to load plugin {path}: {err}");
+            }
+        }
+    }
+}
+
+/// Flipped by the signal handler to ask the accept loop to stop.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(_sig: i32) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Route SIGINT/SIGTERM into [`SHUTDOWN`] so the accept loop can tear down
+/// in an orderly fashion.
+fn install_signal_handler() {
+    unsafe {
+        libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_signal as *const () as libc::sighandler_t);
     }
+}
+
+/// A bound listener plus the worker pool that serves it.
+struct Server {
+    listener: TcpListener,
+    router: Arc<Router>,
+    pool: ThreadPool,
+}
 
-    let request_method = request_line[0];
-    let request_path = request_line[1];
+impl Server {
+    fn bind(addr: &str, router: Router, size: usize) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            router: Arc::new(router),
+            pool: ThreadPool::new(size),
+        })
+    }
+
+    /// Accept connections until SIGINT/SIGTERM arrives, then drain in-flight
+    /// jobs and join the worker threads.
+    fn run_until_signal(mut self) {
+        install_signal_handler();
+        self.listener.set_nonblocking(true).unwrap();
+
+        while !SHUTDOWN.load(Ordering::SeqCst) {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // The job reads and writes with blocking semantics.
+                    stream.set_nonblocking(false).unwrap();
+                    let router = Arc::clone(&self.router);
+                    self.pool.execute(move || {
+                        handle_connection(stream, &router);
+                    });
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => {
+                    eprintln!("accept error: {e}");
+                    break;
+                }
+            }
+        }
+
+        self.pool.shutdown();
+        println!("Shutting down.")
+    }
+}
+
+fn main() {
+    let mut registry = PluginRegistry::new();
+    let mut router = router();
+    load_plugins(&mut registry, &mut router);
 
-    let (response_code, response_body) = match request_method {
-        "HEAD" => (200, String::new()),
-        "GET" => {
-            let mut json_res: HashMap<&str, &str> = HashMap::new();
-            json_res.insert("hello", "world");
-            json_res.insert("test", "ing");
-            (200, json_res.to_json().unwrap())
-        },
-        "POST" => (200, format!("{{\"request_method\": \"{request_method}\"}}")),
-        _ => (405, format!("{{\"request_method\": \"{request_method}\"}}"))
+    let server = Server::bind("127.0.0.1:3000", router, 4).unwrap();
+    server.run_until_signal();
+}
+
+/// Upper bound on the bytes we will buffer for a single request before
+/// replying `413`.
+const MAX_REQUEST_BYTES: usize = 8 * 1024 * 1024;
+
+/// A fully-read request: the start line, header lines, and the complete body.
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<String>,
+    body: Vec<u8>,
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn header_lookup<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find_map(|line| line.split_once(':').filter(|(k, _)| k.trim().eq_ignore_ascii_case(name)))
+        .map(|(_, v)| v.trim())
+}
+
+/// Read a complete request, looping until the `\r\n\r\n` header terminator and
+/// any declared body have arrived. The `Err` value is the status code to reply
+/// with: `400` for malformed input, `413` when it exceeds [`MAX_REQUEST_BYTES`].
+fn read_request(stream: &mut TcpStream) -> Result<Request, u16> {
+    let mut builder = Builder::default();
+    let mut chunk = [0u8; 1024];
+
+    let header_end = loop {
+        if let Some(pos) = find_subslice(builder.as_bytes(), b"\r\n\r\n") {
+            break pos;
+        }
+        if builder.len() > MAX_REQUEST_BYTES {
+            return Err(413);
+        }
+        let read = stream.read(&mut chunk).map_err(|_| 400u16)?;
+        if read == 0 {
+            return Err(400);
+        }
+        builder.append(&chunk[..read]);
+    };
+
+    let buffer = builder.into_bytes();
+    let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut body = buffer[header_end + 4..].to_vec();
+
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().ok_or(400u16)?.split(' ');
+    let method = request_line.next().ok_or(400u16)?.to_string();
+    let path = request_line.next().ok_or(400u16)?.to_string();
+    let headers: Vec<String> = lines.map(|line| line.to_string()).collect();
+
+    if header_lookup(&headers, "Transfer-Encoding")
+        .map(|value| value.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false)
+    {
+        body = read_chunked(stream, body)?;
+    } else if let Some(value) = header_lookup(&headers, "Content-Length") {
+        let content_length: usize = value.parse().map_err(|_| 400u16)?;
+        if content_length > MAX_REQUEST_BYTES {
+            return Err(413);
+        }
+        while body.len() < content_length {
+            let read = stream.read(&mut chunk).map_err(|_| 400u16)?;
+            if read == 0 {
+                return Err(400);
+            }
+            body.extend_from_slice(&chunk[..read]);
+        }
+        body.truncate(content_length);
+    }
+
+    Ok(Request { method, path, headers, body })
+}
+
+/// Decode a `Transfer-Encoding: chunked` body, continuing to read from the
+/// stream past whatever bytes (`prefix`) already arrived with the headers.
+fn read_chunked(stream: &mut TcpStream, prefix: Vec<u8>) -> Result<Vec<u8>, u16> {
+    let mut raw = prefix;
+    let mut pos = 0;
+    let mut out = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    loop {
+        let line_end = loop {
+            if let Some(offset) = find_subslice(&raw[pos..], b"\r\n") {
+                break pos + offset;
+            }
+            let read = stream.read(&mut chunk).map_err(|_| 400u16)?;
+            if read == 0 {
+                return Err(400);
+            }
+            raw.extend_from_slice(&chunk[..read]);
+        };
+
+        let size_field = std::str::from_utf8(&raw[pos..line_end]).map_err(|_| 400u16)?;
+        let size = usize::from_str_radix(size_field.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| 400u16)?;
+        pos = line_end + 2;
+
+        if size == 0 {
+            break;
+        }
+        if out.len() + size > MAX_REQUEST_BYTES {
+            return Err(413);
+        }
+
+        while raw.len() < pos + size + 2 {
+            let read = stream.read(&mut chunk).map_err(|_| 400u16)?;
+            if read == 0 {
+                return Err(400);
+            }
+            raw.extend_from_slice(&chunk[..read]);
+        }
+
+        out.extend_from_slice(&raw[pos..pos + size]);
+        pos += size + 2;
+    }
+
+    Ok(out)
+}
+
+fn handle_connection(mut stream: TcpStream, router: &Router) {
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(code) => {
+            let reason = reason_phrase(code);
+            let bytes = Response::new(code).into_bytes(reason);
+            let _ = stream.write_all(&bytes);
+            let _ = stream.flush();
+            return;
+        }
     };
 
-    let response = format!(
-        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n\r\n{}",
-        response_code,
-        request_path,
-        response_body.len(),
-        response_body
-    );
+    let headers: Vec<&str> = request.headers.iter().map(String::as_str).collect();
+
+    if wants_websocket(&headers) {
+        if let Some(key) = websocket_key(&headers) {
+            handle_websocket(&mut stream, key);
+        }
+        return;
+    }
+
+    let response = maybe_profile(&request.method, &request.path)
+        .unwrap_or_else(|| router.dispatch(&request.method, &request.path, &headers, &request.body));
 
-    stream.write_all(response.as_bytes()).unwrap();
+    let reason = reason_phrase(response.status());
+    let response = response.into_bytes(reason);
+
+    stream.write_all(&response).unwrap();
     stream.flush().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// `read_chunked` takes a concrete `TcpStream`, so tests drive it over a
+    /// real loopback pair rather than an in-memory `Read` mock.
+    fn loopback_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn read_chunked_decodes_multiple_chunks_up_to_last_chunk() {
+        let (mut server, mut client) = loopback_pair();
+        client.write_all(b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n").unwrap();
+
+        let body = read_chunked(&mut server, Vec::new()).unwrap();
+        assert_eq!(body, b"Wikipedia");
+    }
+
+    #[test]
+    fn read_chunked_uses_bytes_already_buffered_in_prefix() {
+        let (mut server, mut client) = loopback_pair();
+        client.write_all(b"0\r\n\r\n").unwrap();
+
+        let body = read_chunked(&mut server, b"3\r\nabc\r\n".to_vec()).unwrap();
+        assert_eq!(body, b"abc");
+    }
+
+    #[test]
+    fn read_chunked_rejects_oversized_chunk() {
+        let (mut server, mut client) = loopback_pair();
+        let huge = format!("{:x}\r\n", MAX_REQUEST_BYTES + 1);
+        client.write_all(huge.as_bytes()).unwrap();
+
+        assert_eq!(read_chunked(&mut server, Vec::new()), Err(413));
+    }
+}